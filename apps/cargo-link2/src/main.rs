@@ -1,11 +1,12 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     env::current_dir,
     path::{Path, PathBuf},
 };
 
+use anstyle::{AnsiColor, Style};
 use anyhow::{Context, Result};
-use cargo_metadata::{Metadata, MetadataCommand};
+use cargo_metadata::{semver::Version, DependencyKind, Metadata, MetadataCommand, PackageId};
 use clap::Parser;
 
 cargo_subcommand_metadata::description!(
@@ -28,39 +29,402 @@ struct Link {
     #[clap(short = 'C', long)]
     dir: Option<PathBuf>,
 
-    /// The target directory to link to the current project.
+    /// Also link crates which are only reachable through dev-dependencies of
+    /// the workspace members.
+    #[clap(long)]
+    include_dev: bool,
+
+    /// Also link crates which are only reachable through build-dependencies
+    /// of the workspace members.
+    ///
+    /// Build-dependencies of other crates are always followed.
+    #[clap(long)]
+    include_build: bool,
+
+    /// Do not modify Cargo.toml.
+    ///
+    /// The `[patch]` section is written to `.cargo/config.toml` of the
+    /// workspace instead, so only Cargo.lock is updated.
+    #[clap(long)]
+    lockfile_only: bool,
+
+    /// Never link these crates, even if they are in the target directories.
+    ///
+    /// Crates listed in `never` of `[workspace.metadata.link]` or
+    /// `[package.metadata.link]` in Cargo.toml are skipped as well.
+    #[clap(long, value_name = "CRATE", value_delimiter = ',')]
+    never: Vec<String>,
+
+    /// Link a crate from crates.io instead of a local directory.
+    ///
+    /// The source of the crate is copied to a cache directory in
+    /// `$CARGO_HOME/link2`, which can be edited freely.
+    #[clap(long = "crate", value_name = "NAME", requires = "version")]
+    krate: Option<String>,
+
+    /// The version of the crate passed to `--crate`.
+    #[clap(long, requires = "krate")]
+    version: Option<String>,
+
+    /// The target directories to link to the current project.
     ///
-    /// If the target directory is a cargo workspace, all packages in the
+    /// If a target directory is a cargo workspace, all packages in the
     /// workspace will be linked.
-    target_dir: PathBuf,
+    #[clap(required_unless_present = "krate")]
+    target_dirs: Vec<PathBuf>,
+
+    /// Print only errors.
+    #[clap(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print the commands being run and the crates which are not used.
+    #[clap(short, long)]
+    verbose: bool,
 }
 
 fn main() -> Result<()> {
     let Subcommand::Link(args) = Subcommand::parse();
 
+    let printer = Printer {
+        verbosity: if args.quiet {
+            Verbosity::Quiet
+        } else if args.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        },
+    };
+
     let working_dir = match args.dir {
         Some(v) => v,
         None => current_dir().context("failed to get current directory")?,
     };
 
-    let link_candidates =
-        list_of_crates(&args.target_dir).context("failed to get candidates for linking")?;
+    let mut target_dirs = args.target_dirs;
+    if let Some(name) = args.krate {
+        let version = args.version.expect("clap requires --version with --crate");
+        target_dirs.push(
+            checkout_registry_crate(&name, &version, &printer)
+                .with_context(|| format!("failed to checkout '{name}@{version}' from crates.io"))?,
+        );
+    }
+
+    let mut link_candidates = Vec::<PatchPkg>::new();
+    for target_dir in &target_dirs {
+        for pkg in list_of_crates(target_dir).context("failed to get candidates for linking")? {
+            if let Some(dup) = link_candidates.iter().find(|c| c.name == pkg.name) {
+                anyhow::bail!(
+                    "'{}' is provided by both '{}' and '{}'",
+                    pkg.name,
+                    dup.path.display(),
+                    pkg.path.display()
+                );
+            }
+            link_candidates.push(pkg);
+        }
+    }
+
+    let dep_kinds = DepKinds {
+        dev: args.include_dev,
+        build: args.include_build,
+    };
+
+    let decisions = add_patch_section(
+        &working_dir,
+        &link_candidates,
+        dep_kinds,
+        args.lockfile_only,
+        &args.never,
+    )
+    .context("failed to add patch section")?;
+
+    let crate_names = decisions
+        .iter()
+        .filter(|(_, reason)| reason.is_linked())
+        .map(|(pkg, _)| pkg.clone())
+        .collect::<Vec<_>>();
+
+    // `cargo update` without `--package` would update every dependency.
+    if !crate_names.is_empty() {
+        run_cargo_update(&working_dir, &crate_names, &printer)
+            .context("failed to run cargo update in the working directory")?;
 
-    let crate_names = add_patch_section(&working_dir, &link_candidates)
-        .context("failed to add patch section to Cargo.toml")?;
+        verify_links(&working_dir, &crate_names).context("some crates were not linked")?;
+    }
 
-    run_cargo_update(&working_dir, &crate_names)
-        .context("failed to run cargo update in the working directory")?;
+    printer.summary(&decisions);
 
     Ok(())
 }
 
+/// Why a candidate is linked or not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reason {
+    /// A workspace member depends on it.
+    Direct,
+    /// Only dependencies of the workspace members depend on it.
+    Transitive,
+    /// It's in the never-link list.
+    Never,
+    /// Only dependencies of a kind which is not followed depend on it.
+    ExcludedKind(DependencyKind),
+    /// It's not in the dependency graph of the current project.
+    Unused,
+}
+
+impl Reason {
+    fn is_linked(self) -> bool {
+        matches!(self, Reason::Direct | Reason::Transitive)
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Reason::Direct => "direct dependency",
+            Reason::Transitive => "transitive dependency",
+            Reason::Never => "listed in `never`",
+            Reason::ExcludedKind(DependencyKind::Development) => {
+                "only a dev-dependency (pass --include-dev)"
+            }
+            Reason::ExcludedKind(DependencyKind::Build) => {
+                "only a build-dependency (pass --include-build)"
+            }
+            Reason::ExcludedKind(_) => "only an excluded dependency",
+            Reason::Unused => "not used by the current project",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Prints cargo-style status messages to stderr.
+struct Printer {
+    verbosity: Verbosity,
+}
+
+impl Printer {
+    const CYAN: Style = AnsiColor::Cyan.on_default().bold();
+    const GREEN: Style = AnsiColor::Green.on_default().bold();
+    const YELLOW: Style = AnsiColor::Yellow.on_default().bold();
+
+    fn status(&self, level: Verbosity, style: Style, status: &str, msg: impl std::fmt::Display) {
+        if self.verbosity >= level {
+            anstream::eprintln!(
+                "{}{status:>12}{} {msg}",
+                style.render(),
+                style.render_reset()
+            );
+        }
+    }
+
+    fn summary(&self, decisions: &[(PatchPkg, Reason)]) {
+        let mut linked = 0;
+        let mut skipped = 0;
+
+        for (pkg, reason) in decisions {
+            let msg = format!(
+                "{} v{} ({}), {}",
+                pkg.name,
+                pkg.version,
+                pkg.path.display(),
+                reason.describe()
+            );
+
+            match reason {
+                Reason::Direct | Reason::Transitive => {
+                    linked += 1;
+                    self.status(Verbosity::Normal, Self::GREEN, "Linked", msg);
+                }
+                Reason::Never | Reason::ExcludedKind(_) => {
+                    skipped += 1;
+                    self.status(Verbosity::Normal, Self::YELLOW, "Skipped", msg);
+                }
+                Reason::Unused => {
+                    skipped += 1;
+                    self.status(Verbosity::Verbose, Self::YELLOW, "Skipped", msg);
+                }
+            }
+        }
+
+        self.status(
+            Verbosity::Normal,
+            Self::GREEN,
+            "Finished",
+            format!("linked {linked} crate(s), skipped {skipped}"),
+        );
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct PatchPkg {
     name: String,
+    version: Version,
     path: PathBuf,
 }
 
+/// Copies the source of `name@version` from crates.io to a cache directory and
+/// returns the path to the copy.
+///
+/// `version` may be partial, like `1.0`, in which case the latest matching
+/// version is used. An existing copy is reused, so local changes are not lost
+/// when linking again.
+fn checkout_registry_crate(name: &str, version: &str, printer: &Printer) -> Result<PathBuf> {
+    validate_crate_spec(name, version)?;
+
+    let cache_dir = cargo_home()?.join("link2");
+    let existing = |version: &str| {
+        let dest = cache_dir.join(format!("{name}-{version}"));
+        if dest.join("Cargo.toml").is_file() {
+            printer.status(
+                Verbosity::Normal,
+                Printer::CYAN,
+                "Using",
+                format!("existing checkout at '{}'", dest.display()),
+            );
+            Some(dest)
+        } else {
+            None
+        }
+    };
+
+    if let Some(dest) = existing(version) {
+        return Ok(dest);
+    }
+
+    // Let cargo download the crate by resolving a throwaway package which
+    // depends on it.
+    let fetch_dir = cache_dir.join(format!("{name}-{version}.fetch"));
+    let fetched = fetch_registry_crate(&fetch_dir, name, version);
+    let _ = std::fs::remove_dir_all(&fetch_dir);
+    let (resolved_version, src_dir) = fetched?;
+
+    if let Some(dest) = existing(&resolved_version.to_string()) {
+        return Ok(dest);
+    }
+
+    let dest = cache_dir.join(format!("{name}-{resolved_version}"));
+    install_checkout(&src_dir, &dest)?;
+
+    printer.status(
+        Verbosity::Normal,
+        Printer::CYAN,
+        "Copied",
+        format!("'{}' to '{}'", src_dir.display(), dest.display()),
+    );
+
+    Ok(dest)
+}
+
+/// Rejects inputs which can't be a crate name or a version, as both are used
+/// in a manifest and in a path.
+fn validate_crate_spec(name: &str, version: &str) -> Result<()> {
+    if name.is_empty()
+        || name.len() > 64
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        anyhow::bail!("'{name}' is not a valid crate name");
+    }
+
+    cargo_metadata::semver::Comparator::parse(&format!("={version}"))
+        .with_context(|| format!("'{version}' is not a valid version"))?;
+
+    Ok(())
+}
+
+/// Resolves `name@version` in a throwaway package at `fetch_dir` and returns
+/// the resolved version and the path to its source in cargo's registry cache.
+fn fetch_registry_crate(fetch_dir: &Path, name: &str, version: &str) -> Result<(Version, PathBuf)> {
+    std::fs::create_dir_all(fetch_dir.join("src"))
+        .with_context(|| format!("failed to create '{}'", fetch_dir.display()))?;
+    std::fs::write(fetch_dir.join("src").join("lib.rs"), "")
+        .with_context(|| format!("failed to write to '{}'", fetch_dir.display()))?;
+    std::fs::write(
+        fetch_dir.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"cargo-link-fetch\"\nversion = \"0.0.0\"\nedition = \
+             \"2021\"\n\n[workspace]\n\n[dependencies]\n{name} = \"={version}\"\n"
+        ),
+    )
+    .with_context(|| format!("failed to write to '{}'", fetch_dir.display()))?;
+
+    let md = MetadataCommand::new()
+        .current_dir(fetch_dir)
+        .exec()
+        .with_context(|| format!("failed to run cargo metadata in '{}'", fetch_dir.display()))?;
+
+    let pkg = direct_dependency(&md, name)
+        .with_context(|| format!("cargo did not resolve '{name}@{version}'"))?;
+
+    Ok((
+        pkg.version.clone(),
+        pkg.manifest_path.parent().unwrap().into(),
+    ))
+}
+
+/// Returns the package which the root package depends on as `name`.
+fn direct_dependency<'a>(md: &'a Metadata, name: &str) -> Option<&'a cargo_metadata::Package> {
+    let resolve = md.resolve.as_ref()?;
+    let root = resolve.root.as_ref()?;
+
+    resolve
+        .nodes
+        .iter()
+        .find(|node| node.id == *root)?
+        .deps
+        .iter()
+        .map(|dep| &md[&dep.pkg])
+        .find(|pkg| pkg.name == name)
+}
+
+/// Copies `src` to `dest` through a temporary directory, so an interrupted
+/// copy is never mistaken for an existing checkout.
+fn install_checkout(src: &Path, dest: &Path) -> Result<()> {
+    // Not `with_extension`, as versions contain dots.
+    let mut tmp = dest.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    if tmp.exists() {
+        std::fs::remove_dir_all(&tmp)
+            .with_context(|| format!("failed to remove '{}'", tmp.display()))?;
+    }
+
+    if let Err(err) = copy_dir_all(src, &tmp) {
+        let _ = std::fs::remove_dir_all(&tmp);
+        return Err(err)
+            .with_context(|| format!("failed to copy '{}' to '{}'", src.display(), tmp.display()));
+    }
+
+    std::fs::rename(&tmp, dest)
+        .with_context(|| format!("failed to move '{}' to '{}'", tmp.display(), dest.display()))
+}
+
+fn copy_dir_all(src: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest)?;
+
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        // Marker file of cargo's registry cache.
+        if entry.file_name() == ".cargo-ok" {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&path, &dest.join(entry.file_name()))?;
+        } else {
+            std::fs::copy(&path, dest.join(entry.file_name()))?;
+        }
+    }
+
+    Ok(())
+}
+
 fn list_of_crates(target_dir: &Path) -> Result<Vec<PatchPkg>> {
     let md = MetadataCommand::new()
         .no_deps()
@@ -76,6 +440,7 @@ fn list_of_crates(target_dir: &Path) -> Result<Vec<PatchPkg>> {
         .filter(|p| ws_members.contains(&p.id))
         .map(|p| PatchPkg {
             name: p.name,
+            version: p.version,
             path: PathBuf::from(p.manifest_path)
                 .parent()
                 .unwrap()
@@ -84,7 +449,33 @@ fn list_of_crates(target_dir: &Path) -> Result<Vec<PatchPkg>> {
         .collect())
 }
 
-fn add_patch_section(working_dir: &Path, link_candidates: &[PatchPkg]) -> Result<Vec<PatchPkg>> {
+/// Kinds of dependencies of the workspace members which are followed while
+/// looking for crates to link. Normal dependencies are always followed, and so
+/// is every dependency of crates outside the workspace, as cargo needs them to
+/// build.
+#[derive(Debug, Clone, Copy)]
+struct DepKinds {
+    dev: bool,
+    build: bool,
+}
+
+impl DepKinds {
+    fn allows(self, kind: DependencyKind) -> bool {
+        match kind {
+            DependencyKind::Development => self.dev,
+            DependencyKind::Build => self.build,
+            _ => true,
+        }
+    }
+}
+
+fn add_patch_section(
+    working_dir: &Path,
+    link_candidates: &[PatchPkg],
+    dep_kinds: DepKinds,
+    lockfile_only: bool,
+    never: &[String],
+) -> Result<Vec<(PatchPkg, Reason)>> {
     let md = MetadataCommand::new()
         .current_dir(working_dir)
         .exec()
@@ -95,47 +486,97 @@ fn add_patch_section(working_dir: &Path, link_candidates: &[PatchPkg]) -> Result
             )
         })?;
 
-    let root_manifest_path = find_root_manifest_path(&md).with_context(|| {
-        format!(
-            "failed to find the root manifest for '{}'",
-            working_dir.display()
-        )
-    })?;
+    let patch_file_path = if lockfile_only {
+        find_cargo_config_path(&md)
+    } else {
+        find_root_manifest_path(&md).with_context(|| {
+            format!(
+                "failed to find the root manifest for '{}'",
+                working_dir.display()
+            )
+        })?
+    };
 
-    let toml = std::fs::read_to_string(&root_manifest_path)
-        .with_context(|| format!("failed to read '{}'", root_manifest_path.display()))?;
+    let UsedCrates {
+        direct: direct_deps,
+        all: all_deps,
+        excluded,
+    } = find_used_crates(&md, link_candidates, dep_kinds)
+        .with_context(|| format!("failed to find used crates in '{}'", working_dir.display()))?;
 
-    let mut doc = toml.parse::<toml_edit::DocumentMut>().with_context(|| {
-        format!(
-            "failed to parse Cargo.toml at '{}'",
-            root_manifest_path.display()
-        )
-    })?;
+    let never = never
+        .iter()
+        .cloned()
+        .chain(never_link_list(&md))
+        .collect::<HashSet<_>>();
 
-    let (_, all_deps) = find_used_crates(&md, link_candidates)
-        .with_context(|| format!("failed to find used crates in '{}'", working_dir.display()))?;
+    let decisions = link_candidates
+        .iter()
+        .map(|pkg| {
+            let reason = if !all_deps.contains(pkg) {
+                match excluded.get(&pkg.name) {
+                    Some(&kind) => Reason::ExcludedKind(kind),
+                    None => Reason::Unused,
+                }
+            } else if never.contains(&pkg.name) {
+                Reason::Never
+            } else if direct_deps.contains(pkg) {
+                Reason::Direct
+            } else {
+                Reason::Transitive
+            };
+            (pkg.clone(), reason)
+        })
+        .collect::<Vec<_>>();
+
+    if !decisions.iter().any(|(_, r)| r.is_linked()) {
+        return Ok(decisions);
+    }
+
+    let toml = match std::fs::read_to_string(&patch_file_path) {
+        Ok(v) => v,
+        // The cargo config file is optional.
+        Err(err) if lockfile_only && err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("failed to read '{}'", patch_file_path.display()))
+        }
+    };
+
+    let mut doc = toml
+        .parse::<toml_edit::DocumentMut>()
+        .with_context(|| format!("failed to parse '{}'", patch_file_path.display()))?;
 
     if doc.get("patch").is_none() {
-        doc["patch"] = toml_edit::table();
+        let mut patch = toml_edit::Table::new();
+        patch.set_implicit(true);
+        doc["patch"] = toml_edit::Item::Table(patch);
     }
 
     let patch = doc["patch"].as_table_mut().unwrap();
     if patch.get("crates-io").is_none() {
-        patch["crates-io"] = toml_edit::table();
+        let mut crates_io = toml_edit::Table::new();
+        crates_io.set_implicit(true);
+        patch["crates-io"] = toml_edit::Item::Table(crates_io);
     }
 
     let crates_io = patch["crates-io"].as_table_mut().unwrap();
 
-    for PatchPkg { name, path } in &all_deps {
+    for (PatchPkg { name, path, .. }, _) in decisions.iter().filter(|(_, r)| r.is_linked()) {
         let mut v = toml_edit::table();
         v["path"] = toml_edit::value(path.display().to_string());
         crates_io[&**name] = v;
     }
 
-    std::fs::write(&root_manifest_path, doc.to_string())
-        .with_context(|| format!("failed to write to '{}'", root_manifest_path.display()))?;
+    if let Some(parent) = patch_file_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create '{}'", parent.display()))?;
+    }
+
+    std::fs::write(&patch_file_path, doc.to_string())
+        .with_context(|| format!("failed to write to '{}'", patch_file_path.display()))?;
 
-    Ok(all_deps)
+    Ok(decisions)
 }
 
 fn find_root_manifest_path(md: &Metadata) -> Result<PathBuf> {
@@ -146,42 +587,127 @@ fn find_root_manifest_path(md: &Metadata) -> Result<PathBuf> {
     }
 }
 
-/// `(direct, all)``
+/// Reads `never` from `[workspace.metadata.link]` and
+/// `[package.metadata.link]` of the root package.
+fn never_link_list(md: &Metadata) -> Vec<String> {
+    [
+        Some(&md.workspace_metadata),
+        md.root_package().map(|p| &p.metadata),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|metadata| metadata.get("link")?.get("never")?.as_array())
+    .flatten()
+    .filter_map(|name| name.as_str().map(String::from))
+    .collect()
+}
+
+/// Returns the path of the cargo config file of the workspace.
+///
+/// The legacy `.cargo/config` is used if it exists, as cargo prefers it over
+/// `.cargo/config.toml`.
+fn find_cargo_config_path(md: &Metadata) -> PathBuf {
+    let dir = PathBuf::from(md.workspace_root.clone()).join(".cargo");
+    let legacy = dir.join("config");
+    if legacy.is_file() {
+        legacy
+    } else {
+        dir.join("config.toml")
+    }
+}
+
+/// Candidates found in the dependency graph of the current project.
+struct UsedCrates {
+    /// Candidates which a workspace member depends on.
+    direct: Vec<PatchPkg>,
+    /// Candidates reachable through the followed dependency kinds.
+    all: Vec<PatchPkg>,
+    /// Names of candidates reachable only through a dependency kind which is
+    /// not followed, with that kind.
+    excluded: HashMap<String, DependencyKind>,
+}
+
 fn find_used_crates(
     md: &Metadata,
     link_candidates: &[PatchPkg],
-) -> Result<(Vec<PatchPkg>, Vec<PatchPkg>)> {
+    dep_kinds: DepKinds,
+) -> Result<UsedCrates> {
     let mut direct_deps = HashSet::new();
     let mut all_deps = HashSet::new();
 
-    let workspace_packages = md
-        .packages
+    // Dependencies are matched by the package they resolve to, so renamed
+    // dependencies (`foo = { package = "bar" }`) and packages whose library
+    // name differs from the package name are handled.
+    let resolve = md
+        .resolve
+        .as_ref()
+        .context("cargo metadata did not include the dependency graph")?;
+    let nodes = resolve
+        .nodes
         .iter()
-        .filter(|p| md.workspace_members.contains(&p.id))
-        .map(|p| p.name.clone())
-        .collect::<HashSet<_>>();
+        .map(|node| (&node.id, node))
+        .collect::<HashMap<_, _>>();
+
+    let find_candidate = |id: &PackageId| {
+        let pkg = &md[id];
+        link_candidates.iter().find(|c| c.name == pkg.name)
+    };
+
+    // Walk the resolved dependency graph from the workspace members, only
+    // following their edges of the requested kinds.
+    let mut visited = HashSet::new();
+    let mut excluded_edges = vec![];
+    let mut queue = md
+        .workspace_members
+        .iter()
+        .map(|id| (id, true))
+        .collect::<Vec<_>>();
 
-    for pkg in &md.packages {
-        if workspace_packages.contains(&pkg.name) {
-            for dep in &pkg.dependencies {
-                if let Some(linked) = link_candidates.iter().find(|c| c.name == dep.name) {
-                    direct_deps.insert(linked.clone());
+    while let Some((id, is_member)) = queue.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+
+        if let Some(linked) = find_candidate(id) {
+            all_deps.insert(linked.name.clone());
+        }
+
+        if let Some(node) = nodes.get(id) {
+            for dep in &node.deps {
+                // Old versions of cargo do not report dependency kinds.
+                if !is_member
+                    || dep.dep_kinds.is_empty()
+                    || dep.dep_kinds.iter().any(|k| dep_kinds.allows(k.kind))
+                {
+                    if is_member {
+                        if let Some(linked) = find_candidate(&dep.pkg) {
+                            direct_deps.insert(linked.clone());
+                        }
+                    }
+
+                    queue.push((&dep.pkg, false));
+                } else if let Some(kind) = dep.dep_kinds.first() {
+                    excluded_edges.push((&dep.pkg, kind.kind));
                 }
             }
         }
     }
 
-    for pkg in &md.packages {
-        if link_candidates.iter().any(|c| c.name == pkg.name) {
-            all_deps.insert(pkg.name.clone());
+    // Walk again from the edges which were not followed, to tell why the
+    // remaining candidates are not linked.
+    let mut excluded = HashMap::new();
+    let mut visited_excluded = HashSet::new();
+    while let Some((id, kind)) = excluded_edges.pop() {
+        if visited.contains(id) || !visited_excluded.insert(id) {
+            continue;
         }
 
-        for dep in &pkg.dependencies {
-            if workspace_packages.contains(&pkg.name) {
-                if let Some(linked) = link_candidates.iter().find(|c| c.name == dep.name) {
-                    direct_deps.insert(linked.clone());
-                }
-            }
+        if let Some(candidate) = find_candidate(id) {
+            excluded.entry(candidate.name.clone()).or_insert(kind);
+        }
+
+        if let Some(node) = nodes.get(id) {
+            excluded_edges.extend(node.deps.iter().map(|dep| (&dep.pkg, kind)));
         }
     }
 
@@ -194,19 +720,31 @@ fn find_used_crates(
         .cloned()
         .collect::<Vec<_>>();
 
-    Ok((direct_deps, all_pkgs))
+    Ok(UsedCrates {
+        direct: direct_deps,
+        all: all_pkgs,
+        excluded,
+    })
 }
 
-fn run_cargo_update(dir: &PathBuf, crates: &[PatchPkg]) -> Result<()> {
+fn run_cargo_update(dir: &PathBuf, crates: &[PatchPkg], printer: &Printer) -> Result<()> {
     let mut cmd = std::process::Command::new(cargo_bin());
     cmd.current_dir(dir);
     cmd.arg("update");
+    if printer.verbosity == Verbosity::Quiet {
+        cmd.arg("--quiet");
+    }
     for pkg in crates {
         cmd.arg("--package");
         cmd.arg(&pkg.name);
     }
 
-    eprintln!("Running: {:?}", cmd);
+    printer.status(
+        Verbosity::Verbose,
+        Printer::CYAN,
+        "Running",
+        format!("{:?}", cmd),
+    );
     let status = cmd.status().context("failed to run cargo update")?;
 
     if !status.success() {
@@ -216,6 +754,327 @@ fn run_cargo_update(dir: &PathBuf, crates: &[PatchPkg]) -> Result<()> {
     Ok(())
 }
 
+/// Checks that each linked crate now resolves to the local path.
+///
+/// Cargo silently ignores a patch if the local version does not satisfy the
+/// version requirements of the dependents, so this reports such crates along
+/// with the requirements.
+fn verify_links(working_dir: &Path, linked: &[PatchPkg]) -> Result<()> {
+    let md = MetadataCommand::new()
+        .current_dir(working_dir)
+        .exec()
+        .with_context(|| {
+            format!(
+                "failed to run cargo metadata in '{}'",
+                working_dir.display()
+            )
+        })?;
+
+    let mut errors = vec![];
+
+    for pkg in linked {
+        let local_dir = canonicalize(&pkg.path);
+        let resolved = md
+            .packages
+            .iter()
+            .filter(|p| p.name == pkg.name)
+            .collect::<Vec<_>>();
+
+        if resolved.iter().any(|p| {
+            p.source.is_none()
+                && p.manifest_path
+                    .parent()
+                    .is_some_and(|dir| canonicalize(dir.as_std_path()) == local_dir)
+        }) {
+            continue;
+        }
+
+        let mut msg = if resolved.is_empty() {
+            format!("'{}' is not in the dependency graph", pkg.name)
+        } else {
+            format!(
+                "'{}' still resolves to {}",
+                pkg.name,
+                resolved
+                    .iter()
+                    .map(|p| format!(
+                        "{} ({})",
+                        p.version,
+                        p.source.as_ref().map_or("path", |s| &s.repr)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        msg.push_str(&format!(
+            "\n  local version {} at '{}' is not used",
+            pkg.version,
+            pkg.path.display()
+        ));
+
+        for dependent in &md.packages {
+            for dep in dependent.dependencies.iter().filter(|d| d.name == pkg.name) {
+                if !dep.req.matches(&pkg.version) {
+                    msg.push_str(&format!(
+                        "\n  '{}' requires '{} {}', which does not match {}",
+                        dependent.name, dep.name, dep.req, pkg.version
+                    ));
+                }
+            }
+        }
+
+        errors.push(msg);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        anyhow::bail!("{}", errors.join("\n"))
+    }
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn cargo_home() -> Result<PathBuf> {
+    if let Some(home) = std::env::var_os("CARGO_HOME") {
+        return Ok(PathBuf::from(home));
+    }
+
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .context("failed to find the home directory")?;
+
+    Ok(PathBuf::from(home).join(".cargo"))
+}
+
 fn cargo_bin() -> String {
     std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(path)
+    }
+
+    /// Copies the fixtures to a fresh directory, so tests can modify them.
+    fn fixture_copy(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cargo-link2-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        copy_dir_all(&fixture(""), &dir).unwrap();
+        dir
+    }
+
+    type Excluded = Vec<(String, DependencyKind)>;
+
+    fn used_crates(dep_kinds: DepKinds) -> (Vec<String>, Vec<String>, Excluded) {
+        let candidates = list_of_crates(&fixture("linked")).unwrap();
+        let md = MetadataCommand::new()
+            .current_dir(fixture("app"))
+            .exec()
+            .unwrap();
+
+        let UsedCrates {
+            direct,
+            all,
+            excluded,
+        } = find_used_crates(&md, &candidates, dep_kinds).unwrap();
+        let names = |pkgs: Vec<PatchPkg>| {
+            let mut names = pkgs.into_iter().map(|p| p.name).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let mut excluded = excluded.into_iter().collect::<Vec<_>>();
+        excluded.sort_by(|a, b| a.0.cmp(&b.0));
+
+        (names(direct), names(all), excluded)
+    }
+
+    #[test]
+    fn verify_linked_path() {
+        let candidates = list_of_crates(&fixture("linked")).unwrap();
+
+        verify_links(&fixture("app"), &candidates).unwrap();
+    }
+
+    #[test]
+    fn verify_unused_patch() {
+        let pkg = PatchPkg {
+            name: "real-name".into(),
+            version: Version::new(0, 2, 0),
+            path: fixture("elsewhere"),
+        };
+
+        let err = format!("{:?}", verify_links(&fixture("app"), &[pkg]).unwrap_err());
+        assert!(err.contains("'real-name' still resolves to 0.1.0"), "{err}");
+        assert!(err.contains("local version 0.2.0"), "{err}");
+    }
+
+    #[test]
+    fn never_link_from_metadata() {
+        let md = MetadataCommand::new()
+            .current_dir(fixture("app"))
+            .exec()
+            .unwrap();
+
+        let mut never = never_link_list(&md);
+        never.sort();
+
+        assert_eq!(never, ["serde", "tokio"]);
+    }
+
+    #[test]
+    fn renamed_dependency() {
+        let (direct, all, _) = used_crates(DepKinds {
+            dev: false,
+            build: false,
+        });
+
+        assert_eq!(direct, ["real-name"]);
+        assert_eq!(all, ["codegen", "real-name"]);
+    }
+
+    #[test]
+    fn dev_dependency() {
+        let (direct, all, excluded) = used_crates(DepKinds {
+            dev: true,
+            build: false,
+        });
+
+        assert_eq!(direct, ["dev-only", "real-name"]);
+        assert_eq!(all, ["codegen", "dev-only", "real-name"]);
+        assert_eq!(excluded, [("build-only".into(), DependencyKind::Build)]);
+    }
+
+    #[test]
+    fn build_dependency() {
+        let (direct, all, excluded) = used_crates(DepKinds {
+            dev: false,
+            build: true,
+        });
+
+        assert_eq!(direct, ["build-only", "real-name"]);
+        assert_eq!(all, ["build-only", "codegen", "real-name"]);
+        assert_eq!(excluded, [("dev-only".into(), DependencyKind::Development)]);
+    }
+
+    #[test]
+    fn lockfile_only() {
+        let dir = fixture_copy("lockfile-only");
+        let manifest = std::fs::read_to_string(dir.join("app/Cargo.toml")).unwrap();
+        let candidates = list_of_crates(&dir.join("linked")).unwrap();
+        let dep_kinds = DepKinds {
+            dev: false,
+            build: false,
+        };
+
+        add_patch_section(&dir.join("app"), &candidates, dep_kinds, true, &[]).unwrap();
+
+        let config = std::fs::read_to_string(dir.join("app/.cargo/config.toml")).unwrap();
+        assert!(
+            config.starts_with("[patch.crates-io.codegen]\n"),
+            "{config}"
+        );
+        assert!(config.contains("[patch.crates-io.real-name]\n"), "{config}");
+        assert!(!config.contains("dev-only"), "{config}");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("app/Cargo.toml")).unwrap(),
+            manifest
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nothing_to_link() {
+        let dir = fixture_copy("nothing-to-link");
+        let dep_kinds = DepKinds {
+            dev: false,
+            build: false,
+        };
+
+        add_patch_section(&dir.join("app"), &[], dep_kinds, true, &[]).unwrap();
+
+        assert!(!dir.join("app/.cargo").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn crate_spec() {
+        validate_crate_spec("serde", "1.0.200").unwrap();
+        validate_crate_spec("serde_json", "1.0").unwrap();
+        validate_crate_spec("cargo-link2", "0.1.5-alpha.1").unwrap();
+
+        validate_crate_spec("", "1.0.0").unwrap_err();
+        validate_crate_spec("../serde", "1.0.0").unwrap_err();
+        validate_crate_spec("serde\"", "1.0.0").unwrap_err();
+        validate_crate_spec("serde", "1.0.0\"\nevil = \"1").unwrap_err();
+        validate_crate_spec("serde", "../../1.0.0").unwrap_err();
+        validate_crate_spec("serde", "1.0, <2").unwrap_err();
+    }
+
+    #[test]
+    fn direct_dependency_by_name() {
+        let md = MetadataCommand::new()
+            .current_dir(fixture("app"))
+            .exec()
+            .unwrap();
+
+        let pkg = direct_dependency(&md, "real-name").unwrap();
+        assert_eq!(pkg.version, Version::new(0, 1, 0));
+        assert!(direct_dependency(&md, "codegen").is_none());
+    }
+
+    #[test]
+    fn install_replaces_partial_copy() {
+        let dir = fixture_copy("install");
+        let dest = dir.join("codegen-0.1.0");
+        let tmp = dir.join("codegen-0.1.0.tmp");
+        std::fs::create_dir_all(&tmp).unwrap();
+        std::fs::write(tmp.join("stale"), "").unwrap();
+
+        install_checkout(&dir.join("linked/codegen"), &dest).unwrap();
+
+        assert!(dest.join("Cargo.toml").is_file());
+        assert!(!dest.join("stale").exists());
+        assert!(!tmp.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn excluded_kind_reason() {
+        let dir = fixture_copy("excluded-kind");
+        let candidates = list_of_crates(&dir.join("linked")).unwrap();
+        let dep_kinds = DepKinds {
+            dev: false,
+            build: false,
+        };
+
+        let decisions =
+            add_patch_section(&dir.join("app"), &candidates, dep_kinds, true, &[]).unwrap();
+        let reason = |name: &str| decisions.iter().find(|(p, _)| p.name == name).unwrap().1;
+
+        assert_eq!(
+            reason("dev-only"),
+            Reason::ExcludedKind(DependencyKind::Development)
+        );
+        assert_eq!(
+            reason("build-only"),
+            Reason::ExcludedKind(DependencyKind::Build)
+        );
+        assert_eq!(reason("codegen"), Reason::Transitive);
+        assert_eq!(reason("real-name"), Reason::Direct);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
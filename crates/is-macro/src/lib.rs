@@ -1,16 +1,15 @@
 extern crate proc_macro;
 
 use heck::ToSnakeCase;
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
 use syn::{
-    parse,
-    parse::Parse,
     parse2, parse_quote,
     punctuated::{Pair, Punctuated},
     spanned::Spanned,
-    Data, DataEnum, DeriveInput, Expr, ExprLit, Field, Fields, Generics, Ident, ImplItem, ItemImpl,
-    Lit, Meta, MetaNameValue, Path, Token, Type, TypePath, TypeReference, TypeTuple, WhereClause,
+    Attribute, Data, DataEnum, DeriveInput, Field, Fields, Generics, Ident, ImplItem, ItemImpl,
+    Lifetime, LitStr, Meta, Path, Token, Type, TypePath, TypeReference, TypeTuple, Visibility,
+    WhereClause,
 };
 
 /// A proc macro to generate methods like is_variant / expect_variant.
@@ -49,17 +48,144 @@ use syn::{
 ///
 /// assert!(Enum::VideoMp4.is_video_mp4());
 /// ```
+///
+/// # Variant names
+///
+/// `#[is(tag_names)]` generates `VARIANTS` and `variant_name()`, using the
+/// same names as `is_*` methods.
+///
+/// ```rust
+/// use is_macro::Is;
+/// #[derive(Debug, Is)]
+/// #[is(tag_names)]
+/// pub enum Enum {
+///     #[is(name = "video_mp4")]
+///     VideoMp4,
+///     ImagePng(u32),
+/// }
+///
+/// assert_eq!(Enum::VARIANTS, &["video_mp4", "image_png"]);
+/// assert_eq!(Enum::ImagePng(1).variant_name(), "image_png");
+/// ```
+///
+/// # Combinators
+///
+/// `#[is(map)]` generates `map_*` and `and_then_*` for variants with unnamed
+/// fields, which transform the value only if it is of the given variant.
+///
+/// ```rust
+/// use is_macro::Is;
+/// #[derive(Debug, PartialEq, Is)]
+/// #[is(map)]
+/// pub enum Enum {
+///     A(u32),
+///     B(String),
+/// }
+///
+/// assert_eq!(Enum::A(1).map_a(|v| v + 1), Enum::A(2));
+/// assert_eq!(Enum::A(1).map_b(|v| v + "!"), Enum::A(1));
+/// assert_eq!(
+///     Enum::A(1).and_then_a(|v| Enum::B(v.to_string())),
+///     Enum::B("1".into())
+/// );
+/// ```
+///
+/// # Extension trait for `Option<&Enum>`
+///
+/// Methods of `Enum` can be called on `&Box<Enum>` as-is. For `Option<&Enum>`,
+/// `#[is(option_ext)]` generates a trait named `EnumOptionExt` with
+/// `is_some_and_*` and `as_*` methods. Use `.as_deref()` to get
+/// `Option<&Enum>` from `Option<Box<Enum>>`.
+///
+/// ```rust
+/// use is_macro::Is;
+/// #[derive(Debug, Is)]
+/// #[is(option_ext)]
+/// pub enum Expr {
+///     Lit(u32),
+///     Paren(Box<Expr>),
+/// }
+///
+/// let expr = Some(Box::new(Expr::Lit(1)));
+///
+/// assert!(expr.as_deref().is_some_and_lit());
+/// assert_eq!(expr.as_deref().as_lit(), Some(&1));
+/// assert!(None::<&Expr>.as_paren().is_none());
+/// ```
+///
+/// # Structs
+///
+/// Deriving `Is` on a struct generates nothing, so a struct can share a derive
+/// list with enums. Unions are rejected.
+///
+/// ```rust
+/// use is_macro::Is;
+/// #[derive(Debug, Is)]
+/// pub struct Marker;
+/// ```
+///
+/// ```rust,compile_fail
+/// use is_macro::Is;
+/// #[derive(Is)]
+/// pub union Bits {
+///     int: u32,
+///     float: f32,
+/// }
+/// ```
 #[proc_macro_derive(Is, attributes(is))]
 pub fn is(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input: DeriveInput = syn::parse(input).expect("failed to parse derive input");
     let generics: Generics = input.generics.clone();
 
-    let items = match input.data {
-        Data::Enum(e) => expand(e),
-        _ => panic!("`Is` can be applied only on enums"),
+    let options = match EnumOptions::from_attrs(&input.attrs) {
+        Ok(v) => v,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let e = match input.data {
+        Data::Enum(e) => e,
+        // Structs have no variants, so there's nothing to generate.
+        Data::Struct(..) => {
+            return match input.attrs.iter().find(|attr| attr.path().is_ident("is")) {
+                Some(attr) => syn::Error::new_spanned(
+                    attr,
+                    "`#[is(...)]` options apply only to enums; remove the attribute from this \
+                     struct",
+                )
+                .to_compile_error()
+                .into(),
+                None => Default::default(),
+            }
+        }
+        Data::Union(..) => {
+            return syn::Error::new_spanned(
+                &input.ident,
+                format!(
+                    "`Is` can be derived only for enums and structs; consider turning `{}` into \
+                     an enum or removing `Is` from the derive list",
+                    input.ident
+                ),
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
+    let option_ext = if options.option_ext {
+        match expand_option_ext(&input.vis, &input.ident, &input.generics, &e) {
+            Ok(tokens) => tokens,
+            Err(err) => return err.to_compile_error().into(),
+        }
+    } else {
+        Default::default()
+    };
+
+    let items = match expand(e, &options) {
+        Ok(items) => items,
+        Err(err) => return err.to_compile_error().into(),
     };
 
-    ItemImpl {
+    let item = ItemImpl {
         attrs: vec![],
         defaultness: None,
         unsafety: None,
@@ -73,92 +199,121 @@ pub fn is(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         brace_token: Default::default(),
         items,
     }
-    .with_generics(generics)
-    .into_token_stream()
+    .with_generics(generics);
+
+    quote!(
+        #item
+        #option_ext
+    )
     .into()
 }
 
-#[derive(Debug)]
-struct Input {
-    name: String,
+/// Options parsed from `#[is(...)]` attributes on the enum.
+#[derive(Debug, Default)]
+struct EnumOptions {
+    /// Generate `VARIANTS` and `variant_name()`.
+    tag_names: bool,
+    /// Generate an extension trait for `Option<&Enum>`.
+    option_ext: bool,
+    /// Generate `map_*` and `and_then_*`.
+    map: bool,
 }
 
-impl Parse for Input {
-    fn parse(input: parse::ParseStream) -> syn::Result<Self> {
-        let _: Ident = input.parse()?;
-        let _: Token![=] = input.parse()?;
-
-        let name = input.parse::<ExprLit>()?;
+impl EnumOptions {
+    fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = EnumOptions::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("is")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag_names") {
+                    options.tag_names = true;
+                    Ok(())
+                } else if meta.path.is_ident("option_ext") {
+                    options.option_ext = true;
+                    Ok(())
+                } else if meta.path.is_ident("map") {
+                    options.map = true;
+                    Ok(())
+                } else {
+                    Err(meta.error(format!(
+                        "unknown option `{}` in `#[is(...)]`",
+                        meta.path.to_token_stream()
+                    )))
+                }
+            })?;
+        }
 
-        Ok(Input {
-            name: match name.lit {
-                Lit::Str(s) => s.value(),
-                _ => panic!("is(name = ...) expects a string literal"),
-            },
-        })
+        Ok(options)
     }
 }
 
-fn expand(input: DataEnum) -> Vec<ImplItem> {
-    let mut items = vec![];
+/// Options parsed from `#[is(...)]` attributes on a variant.
+///
+/// A variant may have any number of `#[is(...)]` attributes, and each
+/// attribute may contain any number of comma-separated options.
+#[derive(Debug, Default)]
+struct VariantOptions {
+    name: Option<String>,
+}
 
-    for v in &input.variants {
-        let attrs = v
-            .attrs
-            .iter()
-            .filter(|attr| attr.path().is_ident("is"))
-            .collect::<Vec<_>>();
-        if attrs.len() >= 2 {
-            panic!("derive(Is) expects no attribute or one attribute")
-        }
-        let i = match attrs.into_iter().next() {
-            None => Input {
-                name: {
-                    v.ident.to_string().to_snake_case()
-                    //
-                },
-            },
-            Some(attr) => {
-                //
-
-                let mut input = Input {
-                    name: Default::default(),
-                };
-
-                let mut apply = |v: &MetaNameValue| {
-                    assert!(
-                        v.path.is_ident("name"),
-                        "Currently, is() only supports `is(name = 'foo')`"
-                    );
+impl VariantOptions {
+    fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = VariantOptions::default();
 
-                    input.name = match &v.value {
-                        Expr::Lit(ExprLit {
-                            lit: Lit::Str(s), ..
-                        }) => s.value(),
-                        _ => unimplemented!(
-                            "is(): name must be a string literal but {:?} is provided",
-                            v.value
-                        ),
-                    };
-                };
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("is")) {
+            if !matches!(attr.meta, Meta::List(..)) {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "expected `#[is(...)]`, e.g. `#[is(name = \"foo\")]`",
+                ));
+            }
 
-                match &attr.meta {
-                    Meta::NameValue(v) => {
-                        //
-                        apply(v)
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    if options.name.is_some() {
+                        return Err(meta.error("duplicate `name` option"));
                     }
-                    Meta::List(l) => {
-                        // Handle is(name = "foo")
-                        input = parse2(l.tokens.clone()).expect("failed to parse input");
-                    }
-                    _ => unimplemented!("is({:?})", attr.meta),
+                    let name: LitStr = meta.value()?.parse()?;
+                    options.name = Some(name.value());
+                    Ok(())
+                } else {
+                    Err(meta.error(format!(
+                        "unknown option `{}` in `#[is(...)]`",
+                        meta.path.to_token_stream()
+                    )))
                 }
+            })?;
+        }
 
-                input
-            }
-        };
+        Ok(options)
+    }
+}
+
+fn expand(input: DataEnum, enum_options: &EnumOptions) -> syn::Result<Vec<ImplItem>> {
+    let mut items = vec![];
+    let mut tag_names = vec![];
 
-        let name = &*i.name;
+    // Patterns are exhaustive for an enum with a single variant, so a
+    // wildcard arm would be unreachable.
+    let fallback = |arm: TokenStream| {
+        if input.variants.len() == 1 {
+            quote!()
+        } else {
+            quote!(_ => #arm,)
+        }
+    };
+    let fallback_false = fallback(quote!(false));
+    let fallback_none = fallback(quote!(None));
+    let fallback_self = fallback(quote!(self));
+    let fallback_panic = fallback(quote!(panic!("called expect on {:?}", self)));
+
+    for v in &input.variants {
+        let options = VariantOptions::from_attrs(&v.attrs)?;
+        let name = options
+            .name
+            .unwrap_or_else(|| v.ident.to_string().to_snake_case());
+        let name = &*name;
+        tag_names.push((&v.ident, name.to_string()));
         {
             let name_of_is = Ident::new(&format!("is_{name}"), v.ident.span());
             let docs_of_is = format!(
@@ -176,7 +331,7 @@ fn expand(input: DataEnum) -> Vec<ImplItem> {
                     pub const fn #name_of_is(&self) -> bool {
                         match *self {
                             Self::#variant { .. } => true,
-                            _ => false,
+                            #fallback_false
                         }
                     }
                 }
@@ -215,8 +370,8 @@ fn expand(input: DataEnum) -> Vec<ImplItem> {
 
             if let Fields::Unnamed(fields) = &v.fields {
                 let types = fields.unnamed.iter().map(|f| f.ty.clone());
-                let cast_ty = types_to_type(types.clone().map(|ty| add_ref(false, ty)));
-                let cast_ty_mut = types_to_type(types.clone().map(|ty| add_ref(true, ty)));
+                let cast_ty = types_to_type(types.clone().map(|ty| add_ref(false, None, ty)));
+                let cast_ty_mut = types_to_type(types.clone().map(|ty| add_ref(true, None, ty)));
                 let ty = types_to_type(types);
 
                 let mut fields: Punctuated<Ident, Token![,]> = fields
@@ -256,7 +411,7 @@ fn expand(input: DataEnum) -> Vec<ImplItem> {
                         pub fn #name_of_cast(&self) -> Option<#cast_ty> {
                             match self {
                                 Self::#variant(#fields) => Some((#fields)),
-                                _ => None,
+                                #fallback_none
                             }
                         }
 
@@ -265,7 +420,7 @@ fn expand(input: DataEnum) -> Vec<ImplItem> {
                         pub fn #name_of_cast_mut(&mut self) -> Option<#cast_ty_mut> {
                             match self {
                                 Self::#variant(#fields) => Some((#fields)),
-                                _ => None,
+                                #fallback_none
                             }
                         }
 
@@ -277,7 +432,7 @@ fn expand(input: DataEnum) -> Vec<ImplItem> {
                         {
                             match self {
                                 Self::#variant(#fields) => (#fields),
-                                _ => panic!("called expect on {:?}", self),
+                                #fallback_panic
                             }
                         }
 
@@ -286,18 +441,191 @@ fn expand(input: DataEnum) -> Vec<ImplItem> {
                         pub fn #name_of_take(self) -> Option<#ty> {
                             match self {
                                 Self::#variant(#fields) => Some((#fields)),
-                                _ => None,
+                                #fallback_none
                             }
                         }
                     }
                 );
 
                 items.extend(item_impl.items);
+
+                // Nothing to transform for variants like `A()`.
+                if enum_options.map && !fields.is_empty() {
+                    let name_of_map = Ident::new(&format!("map_{name}"), v.ident.span());
+                    let name_of_and_then = Ident::new(&format!("and_then_{name}"), v.ident.span());
+
+                    let docs_of_map = format!(
+                        "Applies `f` to the content if `self` is of variant [`{variant}`], and \
+                         returns `self` unchanged otherwise.\n\n[`{variant}`]: #variant.{variant}",
+                        variant = v.ident,
+                    );
+                    let docs_of_and_then = format!(
+                        "Replaces `self` with the result of `f` if `self` is of variant \
+                         [`{variant}`], and returns `self` unchanged otherwise.\n\n[`{variant}`]: \
+                         #variant.{variant}",
+                        variant = v.ident,
+                    );
+
+                    let bindings = fields.iter().collect::<Vec<_>>();
+                    let value = if bindings.len() == 1 {
+                        quote!(#(#bindings)*)
+                    } else {
+                        quote!((#(#bindings),*))
+                    };
+
+                    let item_impl: ItemImpl = parse_quote!(
+                        impl Type {
+                            #[doc = #docs_of_map]
+                            #[inline]
+                            pub fn #name_of_map(self, f: impl FnOnce(#ty) -> #ty) -> Self {
+                                match self {
+                                    Self::#variant(#fields) => {
+                                        let #value = f(#value);
+                                        Self::#variant(#fields)
+                                    }
+                                    #fallback_self
+                                }
+                            }
+
+                            #[doc = #docs_of_and_then]
+                            #[inline]
+                            pub fn #name_of_and_then(self, f: impl FnOnce(#ty) -> Self) -> Self {
+                                match self {
+                                    Self::#variant(#fields) => f(#value),
+                                    #fallback_self
+                                }
+                            }
+                        }
+                    );
+
+                    items.extend(item_impl.items);
+                }
+            }
+        }
+    }
+
+    if enum_options.tag_names {
+        let names = tag_names.iter().map(|(_, name)| name);
+        let arms = tag_names.iter().map(|(variant, name)| {
+            quote!(
+                Self::#variant { .. } => #name,
+            )
+        });
+
+        let item_impl: ItemImpl = parse_quote!(
+            impl Type {
+                /// Names of all variants, in declaration order.
+                pub const VARIANTS: &'static [&'static str] = &[#(#names),*];
+
+                /// Returns the name of the variant of `self`.
+                #[inline]
+                pub const fn variant_name(&self) -> &'static str {
+                    match *self {
+                        #(#arms)*
+                    }
+                }
             }
+        );
+
+        items.extend(item_impl.items);
+    }
+
+    Ok(items)
+}
+
+/// Generates `{Enum}OptionExt`, which provides `is_some_and_*` and `as_*` for
+/// `Option<&Enum>`.
+fn expand_option_ext(
+    vis: &Visibility,
+    ident: &Ident,
+    generics: &Generics,
+    input: &DataEnum,
+) -> syn::Result<TokenStream> {
+    let trait_name = Ident::new(&format!("{ident}OptionExt"), ident.span());
+    let lifetime = Lifetime::new("'__is", Span::call_site());
+
+    let mut trait_generics = generics.clone();
+    trait_generics.params.insert(0, parse_quote!(#lifetime));
+
+    let (impl_generics, trait_ty_generics, where_clause) = trait_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+
+    let mut sigs = vec![];
+    let mut methods = vec![];
+
+    for v in &input.variants {
+        let name = VariantOptions::from_attrs(&v.attrs)?
+            .name
+            .unwrap_or_else(|| v.ident.to_string().to_snake_case());
+        let variant = &v.ident;
+
+        {
+            let name_of_is = Ident::new(&format!("is_some_and_{name}"), v.ident.span());
+            let docs_of_is = format!(
+                "Returns `true` if `self` is `Some` and the value is of variant \
+                 [`{ident}::{variant}`]."
+            );
+
+            sigs.push(quote!(
+                #[doc = #docs_of_is]
+                fn #name_of_is(&self) -> bool;
+            ));
+            methods.push(quote!(
+                #[inline]
+                fn #name_of_is(&self) -> bool {
+                    matches!(self, Some(#ident::#variant { .. }))
+                }
+            ));
+        }
+
+        if let Fields::Unnamed(fields) = &v.fields {
+            let name_of_cast = Ident::new(&format!("as_{name}"), v.ident.span());
+            let docs_of_cast = format!(
+                "Returns `Some` if `self` is `Some` and the value is of variant \
+                 [`{ident}::{variant}`], and `None` otherwise."
+            );
+
+            let cast_ty = types_to_type(
+                fields
+                    .unnamed
+                    .iter()
+                    .map(|f| add_ref(false, Some(lifetime.clone()), f.ty.clone())),
+            );
+            let bindings = (0..fields.unnamed.len())
+                .map(|i| Ident::new(&format!("v{i}"), v.ident.span()))
+                .collect::<Vec<_>>();
+
+            sigs.push(quote!(
+                #[doc = #docs_of_cast]
+                fn #name_of_cast(self) -> Option<#cast_ty>;
+            ));
+            methods.push(quote!(
+                #[inline]
+                fn #name_of_cast(self) -> Option<#cast_ty> {
+                    match self {
+                        Some(#ident::#variant(#(#bindings),*)) => Some((#(#bindings),*)),
+                        _ => None,
+                    }
+                }
+            ));
         }
     }
 
-    items
+    let docs_of_trait =
+        format!("Extension methods for `Option<&{ident}>`, generated by `#[is(option_ext)]`.");
+
+    Ok(quote!(
+        #[doc = #docs_of_trait]
+        #vis trait #trait_name #impl_generics #where_clause {
+            #(#sigs)*
+        }
+
+        impl #impl_generics #trait_name #trait_ty_generics
+            for Option<&#lifetime #ident #ty_generics> #where_clause
+        {
+            #(#methods)*
+        }
+    ))
 }
 
 fn types_to_type(types: impl Iterator<Item = Type>) -> Type {
@@ -313,10 +641,10 @@ fn types_to_type(types: impl Iterator<Item = Type>) -> Type {
     }
 }
 
-fn add_ref(mutable: bool, ty: Type) -> Type {
+fn add_ref(mutable: bool, lifetime: Option<Lifetime>, ty: Type) -> Type {
     Type::Reference(TypeReference {
         and_token: Default::default(),
-        lifetime: None,
+        lifetime,
         mutability: if mutable {
             Some(Default::default())
         } else {
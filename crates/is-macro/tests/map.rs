@@ -0,0 +1,29 @@
+use is_macro::Is;
+
+#[derive(Debug, PartialEq, Is)]
+#[is(map)]
+pub enum Enum<T> {
+    A,
+    B(T),
+    C(usize, usize),
+    D(),
+}
+
+#[test]
+fn map() {
+    assert_eq!(Enum::B(1).map_b(|v| v * 10), Enum::B(10));
+    assert_eq!(Enum::<u32>::A.map_b(|v| v * 10), Enum::A);
+
+    assert_eq!(Enum::<u32>::C(1, 2).map_c(|(a, b)| (b, a)), Enum::C(2, 1));
+    assert_eq!(Enum::B(1).map_c(|(a, b)| (b, a)), Enum::B(1));
+}
+
+#[test]
+fn and_then() {
+    assert_eq!(Enum::B(0).and_then_b(|_| Enum::A), Enum::A);
+    assert_eq!(Enum::<u32>::C(1, 2).and_then_b(|_| Enum::A), Enum::C(1, 2));
+    assert_eq!(
+        Enum::<usize>::C(1, 2).and_then_c(|(a, b)| Enum::B(a + b)),
+        Enum::B(3)
+    );
+}
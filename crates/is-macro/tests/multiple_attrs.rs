@@ -0,0 +1,16 @@
+use is_macro::Is;
+
+#[derive(Debug, Is)]
+pub enum Enum {
+    #[is()]
+    #[is(name = "video_mp4")]
+    VideoMp4,
+    #[is(name = "image_png")]
+    ImagePng(u32),
+}
+
+#[test]
+fn test() {
+    assert!(Enum::VideoMp4.is_video_mp4());
+    assert_eq!(Enum::ImagePng(1).image_png(), Some(1));
+}
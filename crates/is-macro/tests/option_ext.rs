@@ -0,0 +1,29 @@
+use is_macro::Is;
+
+#[derive(Debug, Is)]
+#[is(option_ext)]
+pub enum Enum<'a, T> {
+    A,
+    B(T),
+    #[is(name = "pair")]
+    C(&'a str, Box<Enum<'a, T>>),
+}
+
+#[test]
+fn test() {
+    let a = Enum::<u32>::A;
+    let b = Enum::<u32>::B(1);
+    let c = Enum::C("c", Box::new(Enum::B(2)));
+
+    assert!(Some(&a).is_some_and_a());
+    assert!(!Some(&b).is_some_and_a());
+    assert!(!None::<&Enum<u32>>.is_some_and_a());
+
+    assert_eq!(Some(&b).as_b(), Some(&1));
+    assert_eq!(Some(&a).as_b(), None);
+
+    let (name, inner) = Some(&c).as_pair().unwrap();
+    assert_eq!(*name, "c");
+    assert!(inner.is_b());
+    assert_eq!(Some(&**inner).as_b(), Some(&2));
+}
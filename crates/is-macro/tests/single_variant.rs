@@ -0,0 +1,33 @@
+#![deny(warnings)]
+
+use is_macro::Is;
+
+#[derive(Debug, PartialEq, Is)]
+#[is(map, tag_names)]
+pub enum Enum {
+    A(u32),
+}
+
+#[derive(Debug, Is)]
+pub enum Unit {
+    A,
+}
+
+#[derive(Debug, Is)]
+pub struct Marker;
+
+#[derive(Debug, Is)]
+pub struct Named {
+    pub a: u32,
+}
+
+#[test]
+fn test() {
+    assert!(Enum::A(1).is_a());
+    assert_eq!(Enum::A(1).a(), Some(1));
+    assert_eq!(Enum::A(1).expect_a(), 1);
+    assert_eq!(Enum::A(1).map_a(|v| v + 1), Enum::A(2));
+    assert_eq!(Enum::A(1).variant_name(), "a");
+
+    assert!(Unit::A.is_a());
+}
@@ -0,0 +1,21 @@
+use is_macro::Is;
+
+#[derive(Debug, Is)]
+#[is(tag_names)]
+pub enum Enum<T> {
+    A,
+    B(T),
+    #[is(name = "video_mp4")]
+    VideoMp4 {
+        size: usize,
+    },
+}
+
+#[test]
+fn test() {
+    assert_eq!(Enum::<()>::VARIANTS, &["a", "b", "video_mp4"]);
+
+    assert_eq!(Enum::<()>::A.variant_name(), "a");
+    assert_eq!(Enum::B(1).variant_name(), "b");
+    assert_eq!(Enum::<()>::VideoMp4 { size: 0 }.variant_name(), "video_mp4");
+}
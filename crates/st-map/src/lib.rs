@@ -58,5 +58,197 @@
 //!    );
 //! }
 //! ```
+//!
+//! # Iteration order
+//!
+//! `.iter()`, `.iter_mut()` and `.into_iter()` always yield fields in the
+//! order they are declared in the struct. All of them are
+//! [`DoubleEndedIterator`]s and [`ExactSizeIterator`]s, so `.rev()` and
+//! `.len()` can be used as well.
+//!
+//!```rust
+//! use st_map::StaticMap;
+//!
+//! #[derive(StaticMap)]
+//! struct BrowserData<T> {
+//!    chrome: T,
+//!    safari: T,
+//!    android: T,
+//! }
+//!
+//! let data = BrowserData {
+//!     chrome: 1,
+//!     safari: 2,
+//!     android: 3,
+//! };
+//!
+//! assert_eq!(data.iter().len(), 3);
+//! assert_eq!(
+//!     data.iter().rev().map(|(k, _)| k).collect::<Vec<_>>(),
+//!     vec!["android", "safari", "chrome"]
+//! );
+//! ```
+//!
+//! # Constructor
+//!
+//! `#[static_map(new)]` generates `new`, which takes the values in the field
+//! declaration order.
+//!
+//! As all fields have the same type, reordering the fields of the struct
+//! silently swaps the values passed to `new`. Use a struct literal where the
+//! field order may change.
+//!
+//!```rust
+//! use st_map::StaticMap;
+//!
+//! #[derive(Debug, PartialEq, StaticMap)]
+//! #[static_map(new)]
+//! struct BrowserData<T> {
+//!    chrome: T,
+//!    safari: T,
+//! }
+//!
+//! assert_eq!(
+//!     BrowserData::new(1, 2),
+//!     BrowserData {
+//!         chrome: 1,
+//!         safari: 2
+//!     }
+//! );
+//! ```
+//!
+//! # Checking fields against a key list
+//!
+//! `#[static_map(keys_must_match = "path::to::KEYS")]` makes it a compile
+//! error if the fields do not match a constant `&[&str]` (or `[&str; N]`),
+//! ignoring order.
+//!
+//!```rust
+//! use st_map::StaticMap;
+//!
+//! const BROWSERS: [&str; 2] = ["safari", "chrome"];
+//!
+//! #[derive(StaticMap)]
+//! #[static_map(keys_must_match = "BROWSERS")]
+//! struct BrowserData<T> {
+//!    chrome: T,
+//!    safari: T,
+//! }
+//! ```
+//!
+//!```rust,compile_fail
+//! use st_map::StaticMap;
+//!
+//! const BROWSERS: [&str; 3] = ["safari", "chrome", "android"];
+//!
+//! #[derive(StaticMap)]
+//! #[static_map(keys_must_match = "BROWSERS")]
+//! struct BrowserData<T> {
+//!    chrome: T,
+//!    safari: T,
+//! }
+//! ```
+//!
+//! # Unknown keys
+//!
+//! Indexing with a key which is not a field panics by default. With
+//! `#[static_map(unknown = "default")]`, it returns a reference to a shared
+//! `Default::default()` value instead.
+//!
+//! The value must be `Send + Sync`, as it is stored in a `static`. If the
+//! struct is generic, the value type must also be `'static`, e.g.
+//! `BrowserData<&'static str>` rather than `BrowserData<&'a str>`.
+//!
+//!```rust
+//! use st_map::StaticMap;
+//!
+//! #[derive(Debug, Default, StaticMap)]
+//! #[static_map(unknown = "default")]
+//! struct BrowserData<T: Default> {
+//!    chrome: T,
+//!    safari: T,
+//! }
+//!
+//! let data = BrowserData {
+//!     chrome: Some("100"),
+//!     safari: Some("17"),
+//! };
+//!
+//! assert_eq!(data["chrome"], Some("100"));
+//! assert_eq!(data["netscape"], None);
+//! ```
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{OnceLock, PoisonError, RwLock},
+};
+
 pub use arrayvec;
 pub use static_map_macro::StaticMap;
+
+/// Used by `#[static_map(keys_must_match = "...")]`.
+#[doc(hidden)]
+pub const fn contains_key(keys: &[&str], key: &str) -> bool {
+    let mut i = 0;
+    while i < keys.len() {
+        if str_eq(keys[i], key) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Used by `#[static_map(keys_must_match = "...")]`.
+#[doc(hidden)]
+pub const fn key_count(keys: &[&str]) -> usize {
+    keys.len()
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Returns a reference to a `T::default()` which lives for the rest of the
+/// program.
+///
+/// Used by `#[static_map(unknown = "default")]` on generic structs. The value
+/// is created only once per type, so lookups only take a read lock.
+#[doc(hidden)]
+pub fn default_value<T: Default + Send + Sync + 'static>() -> &'static T {
+    type Values = RwLock<HashMap<TypeId, &'static (dyn Any + Send + Sync)>>;
+    static VALUES: OnceLock<Values> = OnceLock::new();
+
+    let values = VALUES.get_or_init(Default::default);
+
+    let cached = values
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&TypeId::of::<T>())
+        .copied();
+
+    let value = match cached {
+        Some(value) => value,
+        None => *values
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::leak(Box::new(T::default()))),
+    };
+
+    value
+        .downcast_ref()
+        .expect("default_value: type mismatch for TypeId")
+}
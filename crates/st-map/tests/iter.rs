@@ -0,0 +1,57 @@
+use st_map::StaticMap;
+
+#[derive(Debug, PartialEq, StaticMap)]
+pub struct Record<T> {
+    pub a: T,
+    pub b: T,
+    pub c: T,
+}
+
+fn record() -> Record<u32> {
+    Record { a: 1, b: 2, c: 3 }
+}
+
+#[test]
+fn rev() {
+    let mut data = record();
+
+    assert_eq!(
+        data.iter().rev().collect::<Vec<_>>(),
+        vec![("c", &3), ("b", &2), ("a", &1)]
+    );
+
+    for (_, v) in data.iter_mut().rev() {
+        *v *= 10;
+    }
+
+    assert_eq!(
+        data.into_iter().rev().collect::<Vec<_>>(),
+        vec![("c", 30), ("b", 20), ("a", 10)]
+    );
+}
+
+#[test]
+fn both_ends() {
+    let data = record();
+    let mut iter = data.iter();
+
+    assert_eq!(iter.len(), 3);
+    assert_eq!(iter.next(), Some(("a", &1)));
+    assert_eq!(iter.next_back(), Some(("c", &3)));
+    assert_eq!(iter.len(), 1);
+    assert_eq!(iter.next_back(), Some(("b", &2)));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(iter.len(), 0);
+}
+
+#[test]
+fn enumerate() {
+    let mut data = record();
+
+    for (i, (_, v)) in data.iter_mut().enumerate() {
+        *v = i as u32;
+    }
+
+    assert_eq!(data, Record { a: 0, b: 1, c: 2 });
+}
@@ -0,0 +1,30 @@
+use st_map::StaticMap;
+
+pub mod data {
+    pub const KEYS: &[&str] = &["c", "a", "b"];
+}
+
+#[derive(StaticMap)]
+#[static_map(keys_must_match = "data::KEYS")]
+pub struct Record<T> {
+    pub a: T,
+    pub b: T,
+    pub c: T,
+}
+
+pub const ARRAY_KEYS: [&str; 2] = ["a", "b"];
+
+#[derive(StaticMap)]
+#[static_map(keys_must_match = "ARRAY_KEYS", new)]
+pub struct Pair {
+    pub a: u32,
+    pub b: u32,
+}
+
+#[test]
+fn test() {
+    let r = Record { a: 1, b: 2, c: 3 };
+    assert_eq!(r["c"], 3);
+
+    assert_eq!(Pair::new(1, 2)["b"], 2);
+}
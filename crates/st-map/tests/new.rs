@@ -0,0 +1,26 @@
+use st_map::StaticMap;
+
+#[derive(Debug, PartialEq, StaticMap)]
+#[static_map(new)]
+pub struct Record<T: Default> {
+    pub a: T,
+    pub b: T,
+    pub c: T,
+}
+
+#[derive(Debug, PartialEq, StaticMap)]
+#[static_map(new, unknown = "default")]
+pub struct Names {
+    pub a: &'static str,
+    pub b: &'static str,
+}
+
+const NAMES: Names = Names::new("a", "b");
+
+#[test]
+fn test() {
+    assert_eq!(Record::new(1, 2, 3), Record { a: 1, b: 2, c: 3 });
+
+    assert_eq!(NAMES, Names { a: "a", b: "b" });
+    assert_eq!(NAMES["c"], "");
+}
@@ -0,0 +1,68 @@
+use st_map::StaticMap;
+
+#[derive(StaticMap)]
+#[static_map(unknown = "default")]
+pub struct Record<T: Default> {
+    pub a: T,
+    pub b: T,
+}
+
+#[derive(StaticMap)]
+#[static_map(unknown = "default")]
+pub struct Names {
+    pub a: String,
+    pub b: String,
+}
+
+#[test]
+fn generic() {
+    let r = Record { a: 1, b: 2 };
+    assert_eq!(r["a"], 1);
+    assert_eq!(r["b"], 2);
+    assert_eq!(r["c"], 0);
+
+    let r = Record {
+        a: Some(1u8),
+        b: None,
+    };
+    assert_eq!(r["c"], None);
+}
+
+#[test]
+fn no_arg() {
+    let r = Names {
+        a: "a".into(),
+        b: "b".into(),
+    };
+    assert_eq!(r["a"], "a");
+    assert_eq!(r["unknown"], "");
+}
+
+#[test]
+fn threads() {
+    let r = Record { a: 1u64, b: 2 };
+    let n = Names {
+        a: "a".into(),
+        b: "b".into(),
+    };
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                assert_eq!(r["c"], 0);
+                assert_eq!(n["c"], "");
+            });
+        }
+    });
+}
+
+#[test]
+#[should_panic(expected = "Unknown key: c")]
+fn panic_by_default() {
+    #[derive(StaticMap)]
+    pub struct Strict {
+        pub a: u32,
+    }
+
+    let _ = Strict { a: 1 }["c"];
+}
@@ -5,8 +5,8 @@ use std::iter::once;
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
 use syn::{
-    parse, parse_quote, punctuated::Punctuated, token::Comma, Arm, Data, DeriveInput, Expr,
-    ExprMatch, Field, FieldValue, Fields, GenericParam, Generics, Ident, Item, ItemImpl, Lit,
+    parse, parse_quote, punctuated::Punctuated, token::Comma, Arm, Attribute, Data, DeriveInput,
+    Expr, ExprMatch, Field, FieldValue, Fields, GenericParam, Generics, Ident, Item, ItemImpl, Lit,
     LitStr, Pat, PatLit, Token, Type,
 };
 
@@ -20,10 +20,72 @@ enum Mode {
     MutRef,
 }
 
-#[proc_macro_derive(StaticMap)]
+/// What `Index` does for keys which are not a field of the struct.
+#[derive(Default)]
+enum UnknownKey {
+    #[default]
+    Panic,
+    /// Returns a reference to a shared `Default::default()` value.
+    Default,
+}
+
+/// Options parsed from `#[static_map(...)]` attributes on the struct.
+#[derive(Default)]
+struct Options {
+    unknown: UnknownKey,
+    /// Generate a positional `new` constructor.
+    new: bool,
+    /// Path to a constant key list which must match the fields exactly.
+    keys_must_match: Option<syn::Path>,
+}
+
+impl Options {
+    fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = Options::default();
+
+        for attr in attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("static_map"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("unknown") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    options.unknown = match &*value.value() {
+                        "panic" => UnknownKey::Panic,
+                        "default" => UnknownKey::Default,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                value,
+                                "expected `\"panic\"` or `\"default\"`",
+                            ))
+                        }
+                    };
+                    Ok(())
+                } else if meta.path.is_ident("new") {
+                    options.new = true;
+                    Ok(())
+                } else if meta.path.is_ident("keys_must_match") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    options.keys_must_match = Some(value.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown option in `#[static_map(...)]`"))
+                }
+            })?;
+        }
+
+        Ok(options)
+    }
+}
+
+#[proc_macro_derive(StaticMap, attributes(static_map))]
 pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse::<DeriveInput>(input).expect("failed to parse input as DeriveInput");
     let name = input.ident.clone();
+    let options = match Options::from_attrs(&input.attrs) {
+        Ok(v) => v,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
     let fields = match input.data {
         Data::Struct(s) => {
@@ -147,13 +209,27 @@ pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                         comma: Some(Default::default()),
                     }
                 })
-                .chain(once(parse_quote!(
-                    _ => panic!("Unknown key: {}", v),
-                )))
+                .chain(once(match options.unknown {
+                    UnknownKey::Panic => parse_quote!(
+                        _ => panic!("Unknown key: {}", v),
+                    ),
+                    // A function-local static can't name generic parameters, so
+                    // generic structs share a registry keyed by the type.
+                    UnknownKey::Default if input.generics.params.is_empty() => parse_quote!(
+                        _ => {
+                            static DEFAULT: ::std::sync::OnceLock<#data_type> =
+                                ::std::sync::OnceLock::new();
+                            DEFAULT.get_or_init(::std::default::Default::default)
+                        }
+                    ),
+                    UnknownKey::Default => parse_quote!(
+                        _ => st_map::default_value::<#data_type>(),
+                    ),
+                }))
                 .collect(),
         };
 
-        let item: ItemImpl = parse_quote!(
+        let mut item: ItemImpl = parse_quote!(
             impl<'a, K: ?Sized + ::std::borrow::Borrow<str>> ::std::ops::Index<&'a K> for #name {
                 type Output = #data_type;
                 fn index(&self, v: &K) -> &Self::Output {
@@ -163,6 +239,52 @@ pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                 }
             }
         );
+        if matches!(options.unknown, UnknownKey::Default) && !input.generics.params.is_empty() {
+            item.generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote!(#data_type: Default + Send + Sync + 'static));
+        }
+        item.with_generics(input.generics.clone())
+            .to_tokens(&mut tts);
+    }
+
+    if let Some(keys) = &options.keys_must_match {
+        // Evaluated at compile time, so a mismatch is a compile error.
+        let keys_str = keys.to_token_stream().to_string().replace(' ', "");
+        let checks = fields.iter().map(|f| {
+            let f = f.ident.as_ref().unwrap().to_string();
+            let msg = format!("StaticMap: field `{f}` of `{name}` is not in `{keys_str}`");
+            quote!(
+                const _: () = assert!(st_map::contains_key(&#keys, #f), #msg);
+            )
+        });
+        let len_msg =
+            format!("StaticMap: `{keys_str}` does not have exactly the {len} fields of `{name}`");
+
+        quote!(
+            #(#checks)*
+            const _: () = assert!(st_map::key_count(&#keys) == #len, #len_msg);
+        )
+        .to_tokens(&mut tts);
+    }
+
+    if options.new {
+        let names = fields.iter().map(|f| f.ident.as_ref().unwrap());
+        let params = names.clone().map(|f| quote!(#f: #data_type));
+
+        let item: ItemImpl = parse_quote!(
+            impl #name {
+                /// Creates a new map from values in the field declaration order.
+                ///
+                /// The arguments are positional, so reordering the fields
+                /// silently changes which value goes to which field.
+                #[inline]
+                pub const fn new(#(#params),*) -> Self {
+                    Self { #(#names),* }
+                }
+            }
+        );
         item.with_generics(input.generics.clone())
             .to_tokens(&mut tts);
     }
@@ -325,14 +447,12 @@ fn make_iterator(
         .iter()
         .enumerate()
         .map(|(idx, f)| {
-            let pat = idx + 1;
-
             let name = f.ident.as_ref().unwrap();
             let name_str = name.to_string();
             match mode {
-                Mode::Value => quote!(#pat => Some((#name_str, self.data.#name))),
-                Mode::Ref => quote!(#pat => Some((#name_str, &self.data.#name))),
-                Mode::MutRef => quote!(#pat => Some((#name_str, unsafe {
+                Mode::Value => quote!(#idx => Some((#name_str, self.data.#name))),
+                Mode::Ref => quote!(#idx => Some((#name_str, &self.data.#name))),
+                Mode::MutRef => quote!(#idx => Some((#name_str, unsafe {
                     std::mem::transmute::<&mut _, &'a mut _>(&mut self.data.#name)
                 }))),
             }
@@ -342,6 +462,7 @@ fn make_iterator(
     let iter_type = parse_quote!(
         pub struct #iter_type_name #type_generic {
             cur_index: usize,
+            end: usize,
             data: #lifetime #type_name,
         }
     );
@@ -350,8 +471,12 @@ fn make_iterator(
             type Item = (&'static str, #lifetime #data_type);
 
             fn next(&mut self) -> Option<Self::Item> {
+                if self.cur_index == self.end {
+                    return None;
+                }
+                let idx = self.cur_index;
                 self.cur_index += 1;
-                match self.cur_index {
+                match idx {
                     #arms,
 
                     _ => None
@@ -359,12 +484,41 @@ fn make_iterator(
             }
 
             fn size_hint(&self) -> (usize, Option<usize>) {
-                let len = #len - self.cur_index;
+                let len = self.end - self.cur_index;
                 (len, Some(len))
             }
         }
     );
-    iter_impl.generics.where_clause = where_clause;
+    iter_impl.generics.where_clause = where_clause.clone();
+
+    // Each field is yielded at most once from either end, so the mutable
+    // references handed out never alias.
+    let mut double_ended_impl: ItemImpl = parse_quote!(
+        impl #type_generic DoubleEndedIterator for #iter_type_name #generic {
+            fn next_back(&mut self) -> Option<Self::Item> {
+                if self.cur_index == self.end {
+                    return None;
+                }
+                self.end -= 1;
+                match self.end {
+                    #arms,
+
+                    _ => None
+                }
+            }
+        }
+    );
+    double_ended_impl.generics.where_clause = where_clause.clone();
+
+    let mut exact_size_impl: ItemImpl = parse_quote!(
+        impl #type_generic ExactSizeIterator for #iter_type_name #generic {}
+    );
+    exact_size_impl.generics.where_clause = where_clause.clone();
+
+    let mut fused_impl: ItemImpl = parse_quote!(
+        impl #type_generic ::std::iter::FusedIterator for #iter_type_name #generic {}
+    );
+    fused_impl.generics.where_clause = where_clause;
 
     let impl_for_method = {
         let (recv, method_name) = match mode {
@@ -378,6 +532,7 @@ fn make_iterator(
                 pub fn #method_name(#recv) -> #iter_type_name #generic_arg_for_method {
                     #iter_type_name {
                         cur_index: 0,
+                        end: #len,
                         data: self,
                     }
                 }
@@ -385,5 +540,12 @@ fn make_iterator(
         }
     };
 
-    vec![iter_type, Item::Impl(iter_impl), impl_for_method]
+    vec![
+        iter_type,
+        Item::Impl(iter_impl),
+        Item::Impl(double_ended_impl),
+        Item::Impl(exact_size_impl),
+        Item::Impl(fused_impl),
+        impl_for_method,
+    ]
 }
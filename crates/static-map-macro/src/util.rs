@@ -79,19 +79,11 @@ impl ItemImplExt for ItemImpl {
         item.generics
             .params
             .extend(self.generics.params.into_pairs());
-        match self.generics.where_clause {
-            Some(WhereClause {
-                ref mut predicates, ..
-            }) => {
-                println!("FOO");
-                predicates.extend(
-                    generics
-                        .where_clause
-                        .into_iter()
-                        .flat_map(|wc| wc.predicates.into_pairs()),
-                )
-            }
-            ref mut opt @ None => *opt = generics.where_clause,
+        if let Some(WhereClause { predicates, .. }) = self.generics.where_clause {
+            item.generics
+                .make_where_clause()
+                .predicates
+                .extend(predicates);
         }
 
         ItemImpl {